@@ -1,55 +1,29 @@
-use std::{
-    sync::{Arc, Mutex},
-    time::SystemTime,
-};
-
-#[cfg(feature = "ttl")]
-use tokio::task::JoinHandle;
-
-#[derive(Debug, Clone)]
+use std::time::SystemTime;
+
+/// an item paired with an optional expiration timestamp.
+///
+/// this used to spawn a dedicated `tokio::task` per item to enforce the ttl, but that does not
+/// scale to large pools. instead the timestamp is kept as plain data: `DynamicPool`'s background
+/// reaper sweeps these in bulk, and `DynamicPool::try_take` re-checks the timestamp on checkout
+/// so an item that expired between sweeps is never handed out.
+#[derive(Debug)]
 pub(crate) struct ExpiringItem<T> {
-    inner: Arc<Mutex<Option<T>>>,
-    #[cfg(feature = "ttl")]
-    background_task: Option<Arc<JoinHandle<()>>>,
+    inner: T,
+    expires_at: Option<SystemTime>,
 }
 
-impl<T: 'static + Send> ExpiringItem<T> {
+impl<T> ExpiringItem<T> {
     pub(crate) fn new(inner: T, expires_at: Option<SystemTime>) -> Self {
-        let inner = Arc::new(Mutex::new(Some(inner)));
-
-        #[cfg(feature = "ttl")]
-        let background_task = {
-            if let Some(expires_at) = expires_at {
-                let inner = inner.clone();
-                Some(Arc::new(tokio::task::spawn(async move {
-                    let until_expiriation = expires_at.duration_since(SystemTime::now()).unwrap();
-                    tokio::time::sleep(until_expiriation).await;
-                    inner.lock().unwrap().take();
-                })))
-            } else {
-                None
-            }
-        };
-
-        #[cfg(not(feature = "ttl"))]
-        if expires_at.is_some() {
-            panic!("`ttl` feature is disabled");
-        }
-
-        Self {
-            inner,
-            #[cfg(feature = "ttl")]
-            background_task,
-        }
+        Self { inner, expires_at }
     }
 
-    pub(crate) fn take(&self) -> Option<T> {
-        let mut inner = self.inner.lock().unwrap();
-        #[cfg(feature = "ttl")]
-        if let Some(background_task) = &self.background_task {
-            background_task.abort();
-        }
+    pub(crate) fn is_expired(&self) -> bool {
+        self.expires_at
+            .map(|expires_at| expires_at <= SystemTime::now())
+            .unwrap_or(false)
+    }
 
-        inner.take()
+    pub(crate) fn into_inner(self) -> T {
+        self.inner
     }
 }
@@ -2,5 +2,5 @@ mod expiration;
 mod pool;
 mod reset;
 
-pub use self::pool::{DynamicPool, DynamicPoolConfig, DynamicPoolItem};
+pub use self::pool::{DynamicPool, DynamicPoolConfig, DynamicPoolItem, SharedPoolItem};
 pub use self::reset::{DynamicReset, NoopDynamicReset};
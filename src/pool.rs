@@ -1,14 +1,26 @@
 use crossbeam_queue::ArrayQueue;
+#[cfg(feature = "async-take")]
+use crossbeam_queue::SegQueue;
 use dashmap::DashMap;
 use std::fmt::{Debug, Formatter};
 use std::hash::Hash;
 use std::ops::{Deref, DerefMut};
-use std::sync::{Arc, Weak};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
 use std::time::{Duration, SystemTime};
+#[cfg(feature = "async-take")]
+use tokio::sync::oneshot;
 
 use crate::expiration::ExpiringItem;
 use crate::DynamicReset;
 
+/// how often the background reaper sweeps pools for expired items.
+#[cfg(feature = "ttl")]
+const REAPER_INTERVAL: Duration = Duration::from_secs(1);
+
+/// builds a fresh `T` for a given key, used by `DynamicPool::get` when a key's pool is empty.
+type Factory<K, T> = Arc<dyn Fn(&K) -> T + Send + Sync>;
+
 #[derive(Debug, Default)]
 pub struct DynamicPoolConfig {
     pub max_capacity: usize,
@@ -27,27 +39,131 @@ impl DynamicPoolConfig {
     }
 }
 
-#[derive(Debug)]
 pub struct DynamicPool<K: Eq + Hash, T: DynamicReset> {
     inner: Arc<DashMap<K, Arc<PoolData<T>>>>,
     config: Arc<DynamicPoolConfig>,
+    factory: Arc<Mutex<Option<Factory<K, T>>>>,
+    #[cfg(feature = "ttl")]
+    reaper_started: Arc<std::sync::Once>,
+}
+
+impl<K: Eq + Hash, T: DynamicReset> Debug for DynamicPool<K, T> {
+    fn fmt(&self, formatter: &mut Formatter) -> Result<(), std::fmt::Error> {
+        formatter
+            .debug_struct("DynamicPool")
+            .field("config", &self.config)
+            .field("has_factory", &self.factory.lock().unwrap().is_some())
+            .finish()
+    }
 }
 
-impl<K: Eq + Hash, T: DynamicReset + 'static + Send> DynamicPool<K, T> {
+impl<K: Eq + Hash + Send + Sync + 'static, T: DynamicReset + 'static + Send + Sync>
+    DynamicPool<K, T>
+{
     /// creates a new `DynamicPool<T>`.
     pub fn new(config: DynamicPoolConfig) -> Self {
         Self {
             inner: Arc::new(DashMap::new()),
             config: Arc::new(config),
+            factory: Arc::new(Mutex::new(None)),
+            #[cfg(feature = "ttl")]
+            reaper_started: Arc::new(std::sync::Once::new()),
+        }
+    }
+
+    /// registers the factory `get` uses to allocate a fresh item when a key's pool is empty.
+    pub fn register_factory<F>(&self, factory: F)
+    where
+        F: Fn(&K) -> T + Send + Sync + 'static,
+    {
+        *self.factory.lock().unwrap() = Some(Arc::new(factory));
+    }
+
+    /// returns an existing pooled item for `k`, allocating a new one via the registered factory
+    /// if none is available and the pool is below `max_capacity`. if none is available and the
+    /// pool is already at `max_capacity`, blocks the calling thread (retrying briefly) until one
+    /// is returned rather than growing the pool past its configured bound.
+    ///
+    /// # panics
+    ///
+    /// panics if no factory has been registered with `register_factory`.
+    ///
+    /// # blocking
+    ///
+    /// this may block the calling os thread while waiting for capacity to free up. never call
+    /// it from within a tokio task: on a current-thread runtime this can deadlock outright (the
+    /// blocking wait never yields, so the task that would return the outstanding item never
+    /// runs), and on any runtime it starves the executor of that thread for as long as it
+    /// waits. use `DynamicPool::get_async` (behind the `async-take` feature) from async code
+    /// instead.
+    pub fn get(&self, k: &K) -> DynamicPoolItem<T>
+    where
+        K: Clone,
+    {
+        loop {
+            if let Some(item) = self.try_take(k) {
+                return item;
+            }
+
+            #[cfg(feature = "ttl")]
+            if self.config.time_to_live.is_some() {
+                self.spawn_reaper();
+            }
+
+            let pool = self
+                .inner
+                .entry(k.clone())
+                .or_insert_with(|| Arc::new(PoolData::new(self.config.max_capacity)))
+                .clone();
+
+            if self.used(k) + pool.items.len() >= self.config.max_capacity {
+                // already at capacity: wait briefly for a checked-out item to come back
+                // instead of allocating past `max_capacity` via the factory.
+                std::thread::sleep(Duration::from_millis(1));
+                continue;
+            }
+
+            let factory = self
+                .factory
+                .lock()
+                .unwrap()
+                .clone()
+                .expect("`get` requires a factory registered via `register_factory`");
+
+            let object = factory(k);
+            pool.checked_out.fetch_add(1, Ordering::SeqCst);
+            let data = Arc::downgrade(&pool);
+            return DynamicPoolItem {
+                data,
+                object: Some(object),
+                config: self.config.clone(),
+            };
         }
     }
 
     pub fn insert(&self, k: K, item: T) -> Result<(), T> {
-        let pool = self.inner.entry(k).or_insert_with(|| {
-            Arc::new(PoolData {
-                items: ArrayQueue::new(self.config.max_capacity),
-            })
-        });
+        #[cfg(feature = "ttl")]
+        if self.config.time_to_live.is_some() {
+            self.spawn_reaper();
+        }
+
+        let pool = self
+            .inner
+            .entry(k)
+            .or_insert_with(|| Arc::new(PoolData::new(self.config.max_capacity)));
+
+        // a `take()` caller may already be waiting on this key with an empty pool; hand the
+        // item straight to them instead of pushing it into `items`, mirroring the same
+        // waiter-draining logic `DynamicPoolItem::drop` uses on checkin.
+        #[cfg(feature = "async-take")]
+        let mut item = item;
+        #[cfg(feature = "async-take")]
+        while let Some(waiter) = pool.waiters.pop() {
+            match waiter.send(item) {
+                Ok(()) => return Ok(()),
+                Err(returned) => item = returned,
+            }
+        }
 
         let expiration = self
             .config
@@ -55,20 +171,79 @@ impl<K: Eq + Hash, T: DynamicReset + 'static + Send> DynamicPool<K, T> {
             .as_ref()
             .map(|ttl| SystemTime::now() + *ttl);
         let item = ExpiringItem::new(item, expiration);
-        pool.items.push(item).map_err(|e| e.0.take().unwrap())
+        pool.items.push(item).map_err(|e| e.into_inner())
+    }
+
+    /// inserts every item from `items` into `k`'s pool, returning any that didn't fit because
+    /// the pool is already at `max_capacity` - the same overflow behavior as `insert`, just for
+    /// many items at once.
+    pub fn insert_many(&self, k: K, items: impl IntoIterator<Item = T>) -> Vec<T>
+    where
+        K: Clone,
+    {
+        items
+            .into_iter()
+            .filter_map(|item| self.insert(k.clone(), item).err())
+            .collect()
+    }
+
+    /// fills `k`'s pool up to `count` (capped at `max_capacity`) using the registered factory,
+    /// so the allocation cost is paid here instead of on the first hot-path checkout.
+    ///
+    /// # panics
+    ///
+    /// panics if no factory has been registered with `register_factory`.
+    pub fn prewarm(&self, k: &K, count: usize)
+    where
+        K: Clone,
+    {
+        #[cfg(feature = "ttl")]
+        if self.config.time_to_live.is_some() {
+            self.spawn_reaper();
+        }
+
+        let factory = self
+            .factory
+            .lock()
+            .unwrap()
+            .clone()
+            .expect("`prewarm` requires a factory registered via `register_factory`");
+
+        let pool = self
+            .inner
+            .entry(k.clone())
+            .or_insert_with(|| Arc::new(PoolData::new(self.config.max_capacity)))
+            .clone();
+
+        let target = count.min(self.config.max_capacity);
+        while pool.items.len() < target {
+            let expiration = self
+                .config
+                .time_to_live
+                .as_ref()
+                .map(|ttl| SystemTime::now() + *ttl);
+            let item = ExpiringItem::new(factory(k), expiration);
+            if pool.items.push(item).is_err() {
+                break;
+            }
+        }
     }
 
     /// attempts to take an item from a pool, returning `none` if none is available. will never allocate.
     pub fn try_take(&self, k: &K) -> Option<DynamicPoolItem<T>> {
         let pool = self.inner.get(k)?;
         loop {
-            let object = pool.items.pop().ok()?;
-            let object = object.take();
-            if object.is_none() {
+            let item = pool.items.pop()?;
+            if item.is_expired() {
                 continue;
             }
 
-            let object = object.unwrap();
+            let object = item.into_inner();
+            if !object.is_valid() {
+                continue;
+            }
+
+            pool.checked_out.fetch_add(1, Ordering::SeqCst);
             let data = Arc::downgrade(&pool);
             return Some(DynamicPoolItem {
                 data,
@@ -78,7 +253,79 @@ impl<K: Eq + Hash, T: DynamicReset + 'static + Send> DynamicPool<K, T> {
         }
     }
 
-    /// returns the number of objects currently in use in a pool. does not include objects that have been detached.
+    /// attempts to take a shareable item from a pool. if one is already checked out and its
+    /// `DynamicReset::can_share` is `true`, returns another handle to that same object instead
+    /// of removing it from circulation - useful for multiplexable resources like an http/2-style
+    /// connection. non-shareable items fall back to `try_take`'s exclusive behavior: `none` is
+    /// returned and the popped item goes straight back to the pool.
+    pub fn try_take_shared(&self, k: &K) -> Option<SharedPoolItem<T>> {
+        let pool = self.inner.get(k)?;
+
+        // held across the whole check-then-promote sequence: two concurrent callers must not
+        // both see an empty `shared` slot and each detach their own item, since only one of
+        // them could ever end up referenced by `pool.shared` afterwards, orphaning the other.
+        let mut shared = pool.shared.lock().unwrap();
+        if let Some(object) = shared.as_ref().and_then(Weak::upgrade) {
+            return Some(SharedPoolItem {
+                data: Arc::downgrade(&pool),
+                object: Some(object),
+                config: self.config.clone(),
+            });
+        }
+
+        let item = self.try_take(k)?;
+        if !item.can_share() {
+            // not shareable: let `item` drop normally here, returning it to the pool exactly
+            // like any other exclusive checkout.
+            return None;
+        }
+
+        let object = Arc::new(item.detach());
+        *shared = Some(Arc::downgrade(&object));
+        // one promotion, regardless of how many `SharedPoolItem` clones end up pointing at it.
+        pool.shared_checked_out.fetch_add(1, Ordering::SeqCst);
+        Some(SharedPoolItem {
+            data: Arc::downgrade(&pool),
+            object: Some(object),
+            config: self.config.clone(),
+        })
+    }
+
+    /// spawns the single background task that evicts expired items across every key in this
+    /// pool, if one hasn't already been spawned. a pool only ever has one of these, no matter
+    /// how many keys or items it holds.
+    #[cfg(feature = "ttl")]
+    fn spawn_reaper(&self) {
+        self.reaper_started.call_once(|| {
+            let inner = Arc::downgrade(&self.inner);
+            tokio::task::spawn(async move {
+                let mut interval = tokio::time::interval(REAPER_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    let Some(inner) = inner.upgrade() else {
+                        break;
+                    };
+
+                    for pool in inner.iter() {
+                        let pool = pool.value();
+                        for _ in 0..pool.items.capacity() {
+                            let Some(item) = pool.items.pop() else {
+                                break;
+                            };
+
+                            if !item.is_expired() {
+                                pool.items.push(item).ok();
+                            }
+                        }
+                    }
+                }
+            });
+        });
+    }
+
+    /// returns the number of objects currently in use in a pool. does not include objects that
+    /// have been detached. a shared item with several live `SharedPoolItem` clones still only
+    /// counts once, since only one underlying object is actually checked out.
     #[inline]
     pub fn used(&self, k: &K) -> usize {
         let pool = self.inner.get(k);
@@ -87,7 +334,7 @@ impl<K: Eq + Hash, T: DynamicReset + 'static + Send> DynamicPool<K, T> {
         }
 
         let pool = pool.unwrap();
-        Arc::weak_count(&pool)
+        pool.checked_out.load(Ordering::SeqCst) + pool.shared_checked_out.load(Ordering::SeqCst)
     }
 
     #[inline]
@@ -102,18 +349,181 @@ impl<K: Eq + Hash, T: DynamicReset + 'static + Send> DynamicPool<K, T> {
     }
 }
 
+#[cfg(feature = "async-take")]
+impl<K: Eq + Hash + Clone + Send + Sync + 'static, T: DynamicReset + 'static + Send + Sync>
+    DynamicPool<K, T>
+{
+    /// takes an item from a pool, waiting for one to be returned if none is currently available.
+    pub async fn take(&self, k: &K) -> DynamicPoolItem<T> {
+        loop {
+            if let Some(item) = self.try_take(k) {
+                return item;
+            }
+
+            let pool = self
+                .inner
+                .entry(k.clone())
+                .or_insert_with(|| Arc::new(PoolData::new(self.config.max_capacity)))
+                .clone();
+
+            let (tx, rx) = oneshot::channel();
+            pool.waiters.push(tx);
+
+            // an item may have been returned between our failed `try_take` and registering the
+            // waiter above, so check once more now that we're in line.
+            if let Some(item) = self.try_take(k) {
+                return item;
+            }
+
+            if let Ok(object) = rx.await {
+                pool.checked_out.fetch_add(1, Ordering::SeqCst);
+                let data = Arc::downgrade(&pool);
+                return DynamicPoolItem {
+                    data,
+                    object: Some(object),
+                    config: self.config.clone(),
+                };
+            }
+
+            // the sender was dropped without sending us anything; fall back to polling the
+            // queue again rather than propagating a spurious failure to the caller.
+        }
+    }
+
+    /// like `DynamicPool::get`, but waits asynchronously instead of blocking the calling thread
+    /// when the pool is at `max_capacity` - parking on the same waiter queue `take()` uses.
+    /// safe to call from within a tokio task, unlike `get`.
+    ///
+    /// # panics
+    ///
+    /// panics if no factory has been registered with `register_factory`.
+    pub async fn get_async(&self, k: &K) -> DynamicPoolItem<T> {
+        loop {
+            if let Some(item) = self.try_take(k) {
+                return item;
+            }
+
+            #[cfg(feature = "ttl")]
+            if self.config.time_to_live.is_some() {
+                self.spawn_reaper();
+            }
+
+            let pool = self
+                .inner
+                .entry(k.clone())
+                .or_insert_with(|| Arc::new(PoolData::new(self.config.max_capacity)))
+                .clone();
+
+            if self.used(k) + pool.items.len() < self.config.max_capacity {
+                let factory = self
+                    .factory
+                    .lock()
+                    .unwrap()
+                    .clone()
+                    .expect("`get_async` requires a factory registered via `register_factory`");
+
+                let object = factory(k);
+                pool.checked_out.fetch_add(1, Ordering::SeqCst);
+                let data = Arc::downgrade(&pool);
+                return DynamicPoolItem {
+                    data,
+                    object: Some(object),
+                    config: self.config.clone(),
+                };
+            }
+
+            // already at capacity: wait for a checked-out item to come back instead of
+            // allocating past `max_capacity`, the same way `take()` waits for one to appear.
+            let (tx, rx) = oneshot::channel();
+            pool.waiters.push(tx);
+
+            // an item may have been returned (or capacity freed by another detach) between our
+            // checks above and registering the waiter, so check once more now that we're in line.
+            if let Some(item) = self.try_take(k) {
+                return item;
+            }
+            if self.used(k) + pool.items.len() < self.config.max_capacity {
+                continue;
+            }
+
+            if let Ok(object) = rx.await {
+                pool.checked_out.fetch_add(1, Ordering::SeqCst);
+                let data = Arc::downgrade(&pool);
+                return DynamicPoolItem {
+                    data,
+                    object: Some(object),
+                    config: self.config.clone(),
+                };
+            }
+
+            // the sender was dropped without sending us anything; loop back around to recheck
+            // capacity and the queue rather than propagating a spurious failure to the caller.
+        }
+    }
+}
+
 impl<K: Eq + Hash + Clone, T: DynamicReset> Clone for DynamicPool<K, T> {
     fn clone(&self) -> Self {
         Self {
             inner: self.inner.clone(),
             config: self.config.clone(),
+            factory: self.factory.clone(),
+            #[cfg(feature = "ttl")]
+            reaper_started: self.reaper_started.clone(),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Send + Sync + 'static, T: DynamicReset + 'static + Send + Sync>
+    FromIterator<(K, T)> for DynamicPool<K, T>
+{
+    /// builds a pool sized to hold every item up front, pre-populated with them. because
+    /// `max_capacity` is set from the iterator's length, keys with more items than others still
+    /// get that same (larger) per-key capacity.
+    fn from_iter<I: IntoIterator<Item = (K, T)>>(iter: I) -> Self {
+        let items: Vec<(K, T)> = iter.into_iter().collect();
+        let pool = Self::new(DynamicPoolConfig {
+            max_capacity: items.len().max(1),
+            ..Default::default()
+        });
+
+        for (k, item) in items {
+            pool.insert(k, item).ok();
         }
+
+        pool
     }
 }
 
 // data shared by a `DynamicPool`.
 struct PoolData<T> {
     items: ArrayQueue<ExpiringItem<T>>,
+    #[cfg(feature = "async-take")]
+    waiters: SegQueue<oneshot::Sender<T>>,
+    // the currently shared (`can_share() == true`) item for this key, if one is checked out. a
+    // weak reference: once every `SharedPoolItem` holding it drops, it's gone, and the next
+    // `try_take_shared` promotes a fresh exclusive item in its place.
+    shared: Mutex<Option<Weak<T>>>,
+    // number of `DynamicPoolItem`s currently checked out (exclusive use).
+    checked_out: AtomicUsize,
+    // whether a shared item is currently promoted for this key - 0 or 1, incremented once per
+    // `try_take_shared` promotion and decremented once the last `SharedPoolItem` holding it
+    // returns it. deliberately not scaled by how many `SharedPoolItem` clones exist, unlike
+    // `Arc::weak_count`, which every clone's `data` field would otherwise inflate.
+    shared_checked_out: AtomicUsize,
+}
+
+impl<T> PoolData<T> {
+    fn new(max_capacity: usize) -> Self {
+        Self {
+            items: ArrayQueue::new(max_capacity),
+            #[cfg(feature = "async-take")]
+            waiters: SegQueue::new(),
+            shared: Mutex::new(None),
+            checked_out: AtomicUsize::new(0),
+            shared_checked_out: AtomicUsize::new(0),
+        }
+    }
 }
 
 impl<T: DynamicReset + Debug> Debug for PoolData<T> {
@@ -136,6 +546,12 @@ pub struct DynamicPoolItem<T: DynamicReset + 'static + Send> {
 impl<T: DynamicReset + 'static + Send> DynamicPoolItem<T> {
     /// detaches this instance from the pool, returns T.
     pub fn detach(mut self) -> T {
+        // no longer checked out once detached: `used()` shouldn't count it, and it's not going
+        // back to `items` on drop either way now that `self.object` is gone.
+        if let Some(pool) = self.data.upgrade() {
+            pool.checked_out.fetch_sub(1, Ordering::SeqCst);
+        }
+
         self.object
             .take()
             .expect("invariant: object is always `some`.")
@@ -171,8 +587,27 @@ impl<T: DynamicReset + 'static + Send> DerefMut for DynamicPoolItem<T> {
 impl<T: DynamicReset + 'static + Send> Drop for DynamicPoolItem<T> {
     fn drop(&mut self) {
         if let Some(mut object) = self.object.take() {
+            let pool = self.data.upgrade();
+            if let Some(pool) = &pool {
+                pool.checked_out.fetch_sub(1, Ordering::SeqCst);
+            }
+
             object.reset();
-            if let Some(pool) = self.data.upgrade() {
+            if !object.is_valid() {
+                return;
+            }
+
+            if let Some(pool) = pool {
+                #[cfg(feature = "async-take")]
+                while let Some(waiter) = pool.waiters.pop() {
+                    match waiter.send(object) {
+                        Ok(()) => return,
+                        // the waiter's receiver was dropped (its `take()` future was
+                        // cancelled); try handing the object to the next one in line.
+                        Err(returned) => object = returned,
+                    }
+                }
+
                 let expiration = self
                     .config
                     .time_to_live
@@ -184,3 +619,74 @@ impl<T: DynamicReset + 'static + Send> Drop for DynamicPoolItem<T> {
         }
     }
 }
+
+/// a handle to a shareable object checked out from a dynamic pool via `try_take_shared`. may
+/// have sibling `SharedPoolItem`s pointing at the same underlying object; only the last one
+/// dropped returns it to the pool.
+#[derive(Debug)]
+pub struct SharedPoolItem<T: DynamicReset + 'static + Send> {
+    data: Weak<PoolData<T>>,
+    object: Option<Arc<T>>,
+    config: Arc<DynamicPoolConfig>,
+}
+
+impl<T: DynamicReset + 'static + Send> Clone for SharedPoolItem<T> {
+    fn clone(&self) -> Self {
+        Self {
+            data: self.data.clone(),
+            object: self.object.clone(),
+            config: self.config.clone(),
+        }
+    }
+}
+
+impl<T: DynamicReset + 'static + Send> AsRef<T> for SharedPoolItem<T> {
+    fn as_ref(&self) -> &T {
+        self.object
+            .as_deref()
+            .expect("invariant: object is always `some`.")
+    }
+}
+
+impl<T: DynamicReset + 'static + Send> Deref for SharedPoolItem<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.object
+            .as_deref()
+            .expect("invariant: object is always `some`.")
+    }
+}
+
+impl<T: DynamicReset + 'static + Send> Drop for SharedPoolItem<T> {
+    fn drop(&mut self) {
+        let Some(object) = self.object.take() else {
+            return;
+        };
+        let Some(pool) = self.data.upgrade() else {
+            return;
+        };
+
+        // held for the whole unwrap attempt so two holders dropping at once can't both see a
+        // strong count that looks like "last holder" and race to return the object twice.
+        let mut shared = pool.shared.lock().unwrap();
+        if let Ok(mut object) = Arc::try_unwrap(object) {
+            *shared = None;
+            pool.shared_checked_out.fetch_sub(1, Ordering::SeqCst);
+            drop(shared);
+
+            object.reset();
+            if !object.is_valid() {
+                return;
+            }
+
+            let expiration = self
+                .config
+                .time_to_live
+                .as_ref()
+                .map(|ttl| SystemTime::now() + *ttl);
+            pool.items.push(ExpiringItem::new(object, expiration)).ok();
+        }
+        // else: other holders remain, nothing to do - `shared` still points at the live object.
+    }
+}
@@ -0,0 +1,31 @@
+/// resets an object so it's safe to hand back out from a pool.
+pub trait DynamicReset {
+    /// resets `self` back to a clean state before it's returned to the pool.
+    fn reset(&mut self);
+
+    /// returns whether `self` is still usable and safe to hand out from the pool. called on
+    /// checkout (after popping) and on checkin (after `reset`) so objects that silently died
+    /// while idle — a closed socket, an expired handle — are discarded instead of reused.
+    ///
+    /// defaults to always valid, matching today's behavior for types that can't go stale.
+    fn is_valid(&self) -> bool {
+        true
+    }
+
+    /// returns whether several holders may legitimately use `self` at once (e.g. a multiplexed
+    /// http/2-style connection). when true, `DynamicPool::try_take_shared` hands out clones of
+    /// the same underlying object instead of removing it from circulation.
+    ///
+    /// defaults to false, matching today's exclusive-use behavior.
+    fn can_share(&self) -> bool {
+        false
+    }
+}
+
+/// a `DynamicReset` implementation that does nothing.
+#[derive(Debug, Default)]
+pub struct NoopDynamicReset;
+
+impl DynamicReset for NoopDynamicReset {
+    fn reset(&mut self) {}
+}
@@ -51,6 +51,317 @@ fn reset() {
     assert_eq!(person.age, 0);
 }
 
+#[derive(Default, Debug)]
+struct Connection {
+    closed: bool,
+}
+
+impl DynamicReset for Connection {
+    fn reset(&mut self) {}
+
+    fn is_valid(&self) -> bool {
+        !self.closed
+    }
+}
+
+#[test]
+fn dead_items_are_discarded_on_checkout_and_checkin() {
+    let pool = DynamicPool::new(DynamicPoolConfig {
+        max_capacity: 10,
+        ..Default::default()
+    });
+
+    pool.insert("conn", Connection { closed: true }).unwrap();
+    assert!(pool.try_take(&"conn").is_none());
+
+    pool.insert("conn", Connection::default()).unwrap();
+    let mut conn = pool.try_take(&"conn").unwrap();
+    conn.closed = true;
+    drop(conn);
+
+    assert!(pool.try_take(&"conn").is_none());
+}
+
+#[derive(Default, Debug)]
+struct MultiplexedConnection {
+    uses: u32,
+}
+
+impl DynamicReset for MultiplexedConnection {
+    fn reset(&mut self) {
+        self.uses = 0;
+    }
+
+    fn can_share(&self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn try_take_shared_hands_out_clones_of_the_same_item() {
+    let pool = DynamicPool::new(DynamicPoolConfig {
+        max_capacity: 10,
+        ..Default::default()
+    });
+
+    pool.insert("conn", MultiplexedConnection::default())
+        .unwrap();
+
+    let a = pool.try_take_shared(&"conn").unwrap();
+    let b = pool.try_take_shared(&"conn").unwrap();
+    assert_eq!(a.uses, b.uses);
+
+    // the only item is out with the shared holders, so `try_take` has nothing to hand out.
+    assert!(pool.try_take(&"conn").is_none());
+
+    drop(a);
+    // `b` is still holding it, so it must not have been returned to the pool yet.
+    assert!(pool.try_take(&"conn").is_none());
+
+    drop(b);
+    // the last holder dropped, so the (reset) item is back in the pool.
+    assert!(pool.try_take(&"conn").is_some());
+}
+
+#[test]
+fn used_does_not_inflate_with_shared_item_clones() {
+    let pool = DynamicPool::new(DynamicPoolConfig {
+        max_capacity: 10,
+        ..Default::default()
+    });
+
+    pool.insert("conn", MultiplexedConnection::default())
+        .unwrap();
+
+    let first = pool.try_take_shared(&"conn").unwrap();
+    let clones: Vec<_> = (0..5).map(|_| first.clone()).collect();
+    assert_eq!(pool.used(&"conn"), 1);
+
+    drop(clones);
+    // all clones gone but the original handle remains, so it's still checked out.
+    assert_eq!(pool.used(&"conn"), 1);
+
+    drop(first);
+    assert_eq!(pool.used(&"conn"), 0);
+}
+
+#[test]
+fn try_take_shared_promotes_only_one_item_under_concurrency() {
+    let pool = DynamicPool::new(DynamicPoolConfig {
+        max_capacity: 10,
+        ..Default::default()
+    });
+
+    pool.insert_many("conn", (0..4).map(|_| MultiplexedConnection::default()));
+
+    let barrier = std::sync::Arc::new(std::sync::Barrier::new(4));
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let pool = pool.clone();
+            let barrier = barrier.clone();
+            std::thread::spawn(move || {
+                barrier.wait();
+                pool.try_take_shared(&"conn")
+            })
+        })
+        .collect();
+
+    let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+    // every caller that got a handle must be pointing at the same promoted item - if two of
+    // them had raced past the shared-slot check independently, they'd each detach and promote
+    // their own, orphaning one from the pool's single-shared-instance bookkeeping.
+    let first = results
+        .iter()
+        .find_map(|r| r.as_ref())
+        .expect("at least one caller should get a shared handle");
+    for result in results.iter().flatten() {
+        assert!(std::ptr::eq(&**result, &**first));
+    }
+}
+
+#[test]
+fn try_take_shared_falls_back_for_non_shareable_items() {
+    let pool = DynamicPool::new(DynamicPoolConfig {
+        max_capacity: 10,
+        ..Default::default()
+    });
+
+    pool.insert("hello_world", Person::default()).unwrap();
+    assert!(pool.try_take_shared(&"hello_world").is_none());
+    // the popped item was returned to the pool instead of being lost.
+    assert!(pool.try_take(&"hello_world").is_some());
+}
+
+#[test]
+fn insert_many_returns_overflow() {
+    let pool = DynamicPool::new(DynamicPoolConfig {
+        max_capacity: 2,
+        ..Default::default()
+    });
+
+    let overflow = pool.insert_many(
+        "hello_world",
+        (0..3).map(|age| Person {
+            name: String::new(),
+            age,
+        }),
+    );
+
+    assert_eq!(overflow.len(), 1);
+    assert_eq!(pool.used(&"hello_world"), 0);
+    let first = pool.try_take(&"hello_world");
+    let second = pool.try_take(&"hello_world");
+    assert!(first.is_some());
+    assert!(second.is_some());
+    assert!(pool.try_take(&"hello_world").is_none());
+}
+
+#[test]
+fn prewarm_fills_pool_via_factory() {
+    let pool = DynamicPool::new(DynamicPoolConfig {
+        max_capacity: 2,
+        ..Default::default()
+    });
+
+    pool.register_factory(|_: &&str| Person::default());
+    pool.prewarm(&"hello_world", 5);
+
+    let first = pool.try_take(&"hello_world");
+    let second = pool.try_take(&"hello_world");
+    assert!(first.is_some());
+    assert!(second.is_some());
+    assert!(pool.try_take(&"hello_world").is_none());
+}
+
+#[test]
+fn from_iter_builds_prepopulated_pool() {
+    let pool: DynamicPool<&str, Person> = [
+        ("a", Person::default()),
+        ("b", Person::default()),
+        ("b", Person::default()),
+    ]
+    .into_iter()
+    .collect();
+
+    assert!(pool.try_take(&"a").is_some());
+    assert!(pool.try_take(&"b").is_some());
+    assert!(pool.try_take(&"b").is_some());
+}
+
+#[test]
+fn get_allocates_via_factory_when_empty() {
+    let pool = DynamicPool::new(DynamicPoolConfig {
+        max_capacity: 10,
+        ..Default::default()
+    });
+
+    pool.register_factory(|_: &&str| Person {
+        name: String::from("factory"),
+        age: 1,
+    });
+
+    let person = pool.get(&"hello_world");
+    assert_eq!(person.name, "factory");
+    drop(person);
+
+    // the item returned above is back in the pool, so this should reuse it instead of
+    // allocating another one.
+    let person = pool.get(&"hello_world");
+    assert_eq!(person.name, "");
+}
+
+#[test]
+fn get_blocks_at_max_capacity_instead_of_overallocating() {
+    let pool = DynamicPool::new(DynamicPoolConfig {
+        max_capacity: 1,
+        ..Default::default()
+    });
+
+    pool.register_factory(|_: &&str| Person::default());
+
+    let first = pool.get(&"hello_world");
+
+    let pool_clone = pool.clone();
+    let second = std::thread::spawn(move || pool_clone.get(&"hello_world"));
+
+    // give `second` a chance to (wrongly) return immediately before we release `first`.
+    std::thread::sleep(Duration::from_millis(20));
+    assert!(!second.is_finished());
+
+    drop(first);
+    second.join().unwrap();
+}
+
+#[cfg(feature = "async-take")]
+#[tokio::test]
+async fn get_async_waits_without_blocking_the_runtime() {
+    // default `#[tokio::test]` flavor is a single-threaded runtime: if `get_async` ever blocked
+    // the thread instead of parking, the spawned task below could never run and this would hang.
+    let pool = DynamicPool::new(DynamicPoolConfig {
+        max_capacity: 1,
+        ..Default::default()
+    });
+
+    pool.register_factory(|_: &&str| Person::default());
+
+    let first = pool.get(&"hello_world");
+
+    let pool_clone = pool.clone();
+    let waiter = tokio::spawn(async move { pool_clone.get_async(&"hello_world").await });
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    drop(first);
+
+    let second = tokio::time::timeout(Duration::from_millis(200), waiter)
+        .await
+        .expect("get_async should resolve once capacity frees up")
+        .unwrap();
+    assert!(second.name.is_empty());
+}
+
+#[cfg(feature = "async-take")]
+#[tokio::test]
+async fn take_waits_for_available_item() {
+    let pool = DynamicPool::new(DynamicPoolConfig {
+        max_capacity: 10,
+        ..Default::default()
+    });
+
+    pool.insert("hello_world", Person::default()).unwrap();
+    let person = pool.try_take(&"hello_world").unwrap();
+
+    let pool_clone = pool.clone();
+    let waiter = tokio::spawn(async move { pool_clone.take(&"hello_world").await });
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    drop(person);
+
+    let person = waiter.await.unwrap();
+    assert!(person.name.is_empty());
+}
+
+#[cfg(feature = "async-take")]
+#[tokio::test]
+async fn insert_wakes_a_pending_take_waiter() {
+    let pool = DynamicPool::new(DynamicPoolConfig {
+        max_capacity: 10,
+        ..Default::default()
+    });
+
+    let pool_clone: DynamicPool<&str, Person> = pool.clone();
+    let waiter = tokio::spawn(async move { pool_clone.take(&"hello_world").await });
+
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    pool.insert("hello_world", Person::default()).unwrap();
+
+    let person = tokio::time::timeout(Duration::from_millis(100), waiter)
+        .await
+        .expect("insert() should have woken the waiting take()")
+        .unwrap();
+    assert!(person.name.is_empty());
+}
+
 #[tokio::test]
 async fn time_to_live() {
     let pool = DynamicPool::new(DynamicPoolConfig {